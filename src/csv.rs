@@ -1,29 +1,25 @@
+use crate::database::{Column, ColumnType, Datastore, Value};
+use std::io::BufRead;
 use std::path::Path;
-use std::{io, fs};
-use crate::database::{Datastore, Column};
+use std::{fs, io};
 
+/// Opens `path` and reads just its header row; the actual cells are left on disk until a
+/// specific column is requested via [`Datastore::column`].
 pub fn read_csv(path: &Path, name: &str) -> io::Result<Datastore> {
-    let file = fs::read_to_string(path)?;
-    let split = file
-        .split("\n")
-        .filter(|line| !line.is_empty())
-        .map(|line| line.replace("\r", ""))
-        .map(|line| {
-            line.split(",")
-                .map(|x| x.to_owned())
-                .collect::<Vec<String>>()
-        })
-        .collect::<Vec<_>>();
-
-    let header = match split.first() {
-        None => {
-            return Err(io::Error::new(
+    let header_line = io::BufReader::new(fs::File::open(path)?)
+        .lines()
+        .next()
+        .ok_or_else(|| {
+            io::Error::new(
                 io::ErrorKind::InvalidData,
                 format!("No header in CSV: {}", path.display()),
-            ));
-        }
-        Some(v) => v,
-    };
+            )
+        })??;
+
+    let header = header_line
+        .split(',')
+        .map(|h| h.to_owned())
+        .collect::<Vec<_>>();
 
     if header.is_empty() {
         return Err(io::Error::new(
@@ -32,45 +28,300 @@ pub fn read_csv(path: &Path, name: &str) -> io::Result<Datastore> {
         ));
     }
 
-    let data = split.iter().skip(1).collect::<Vec<_>>();
+    Ok(Datastore {
+        path: path.to_owned(),
+        name: name.to_owned(),
+        header,
+    })
+}
 
-    let mut columns = header
-        .into_iter()
-        .map(|h| Column {
-            name: (*h).to_owned(),
-            data: vec![],
+/// Reads `names`' cells from `datastore`'s file in a single pass, validating every data
+/// row's width against the header. The whole file is read into memory as one `String`,
+/// and only the requested fields are then copied out of it - every other field is scanned
+/// (to find its boundaries, respecting quoting) and then dropped - so the file is read
+/// once no matter how many columns are requested, and the per-cell allocations stay
+/// proportional to the columns asked for. Peak memory still includes the whole file's
+/// text.
+pub fn read_columns(datastore: &Datastore, names: &[&str]) -> io::Result<Vec<Column>> {
+    let indices = names
+        .iter()
+        .map(|name| {
+            datastore
+                .header
+                .iter()
+                .position(|h| h == name)
+                .ok_or_else(|| {
+                    io::Error::new(
+                        io::ErrorKind::NotFound,
+                        format!("No column: {} found", name),
+                    )
+                })
         })
-        .collect::<Vec<_>>();
+        .collect::<io::Result<Vec<_>>>()?;
 
-    for row in data {
-        if row.len() != header.len() {
-            return Err(io::Error::new(
-                io::ErrorKind::InvalidData,
-                format!(
-                    "Inconsistent amount of data in row: {:?} (should have: {} columns, has: {})",
-                    row,
-                    header.len(),
-                    row.len()
-                ),
-            ));
+    let file = fs::read_to_string(&datastore.path)?;
+
+    let mut cells: Vec<Vec<String>> = vec![vec![]; indices.len()];
+    read_selected_fields(&file, datastore.header.len(), &indices, |record| {
+        for (slot, cell) in record.into_iter().enumerate() {
+            cells[slot].push(cell);
+        }
+    })?;
+
+    Ok(names
+        .iter()
+        .zip(cells)
+        .map(|(name, cells)| {
+            let column_type = infer_column_type(&cells);
+            let data = cells.iter().map(|c| to_value(c, column_type)).collect();
+            Column {
+                name: (*name).to_owned(),
+                data,
+                column_type,
+            }
+        })
+        .collect())
+}
+
+/// Reads `name`'s cells from `datastore`'s file. See [`read_columns`] for how a single
+/// column (or several) gets pulled out of the table without building the other columns
+/// (the whole file is still read into memory).
+pub fn read_column(datastore: &Datastore, name: &str) -> io::Result<Column> {
+    Ok(read_columns(datastore, &[name])?.remove(0))
+}
+
+/// Parses RFC 4180 CSV text, invoking `on_record` with the cells at `targets` (in the
+/// same order as `targets`) once per data record, skipping the header row. Returns an
+/// error if any data record doesn't have exactly `expected_field_count` fields. A field
+/// wrapped in double quotes is read as a single field even when it contains commas or
+/// embedded newlines; `""` inside a quoted field unescapes to a literal `"`. A record
+/// only ends on a newline outside quotes.
+fn read_selected_fields(
+    input: &str,
+    expected_field_count: usize,
+    targets: &[usize],
+    mut on_record: impl FnMut(Vec<String>),
+) -> io::Result<()> {
+    let mut skipped_header = false;
+    let mut captured: Vec<String> = vec![String::new(); targets.len()];
+    let mut field = String::new();
+    let mut field_index = 0usize;
+    let mut field_is_target = targets.contains(&0);
+    let mut in_quotes = false;
+    // tracked separately from `field`, which stays empty for a field that isn't a target
+    let mut record_is_blank = true;
+    let mut chars = input.chars().peekable();
+
+    while let Some(c) = chars.next() {
+        if c != '\r' && c != '\n' {
+            record_is_blank = false;
         }
-        for i in 0..row.len() {
-            let val = (*row.get(i).unwrap()).to_owned();
-            columns.get_mut(i).unwrap().data.push(val);
+        if in_quotes {
+            if c == '"' {
+                if chars.peek() == Some(&'"') {
+                    if field_is_target {
+                        field.push('"');
+                    }
+                    chars.next();
+                } else {
+                    in_quotes = false;
+                }
+            } else if field_is_target {
+                field.push(c);
+            }
+            continue;
+        }
+
+        match c {
+            '"' => in_quotes = true,
+            ',' => {
+                store_field(&mut field, field_index, targets, &mut captured);
+                field_index += 1;
+                field_is_target = targets.contains(&field_index);
+            }
+            '\r' => {} // a following '\n' ends the record; a bare '\r' is dropped
+            '\n' => {
+                let first_field_empty = record_is_blank;
+                store_field(&mut field, field_index, targets, &mut captured);
+                field_index += 1;
+                emit_record(
+                    field_index,
+                    first_field_empty,
+                    expected_field_count,
+                    &mut skipped_header,
+                    &mut captured,
+                    targets,
+                    &mut on_record,
+                )?;
+                field_index = 0;
+                field_is_target = targets.contains(&0);
+                record_is_blank = true;
+            }
+            _ => {
+                if field_is_target {
+                    field.push(c);
+                }
+            }
         }
     }
 
-    Ok(Datastore {
-        path: path.to_owned(),
-        name: name.to_owned(),
-        columns,
-    })
+    // the last record has no trailing newline
+    if !record_is_blank {
+        let first_field_empty = record_is_blank;
+        store_field(&mut field, field_index, targets, &mut captured);
+        field_index += 1;
+        emit_record(
+            field_index,
+            first_field_empty,
+            expected_field_count,
+            &mut skipped_header,
+            &mut captured,
+            targets,
+            &mut on_record,
+        )?;
+    }
+
+    Ok(())
+}
+
+/// Copies `field` into every slot of `captured` whose target index is `field_index`,
+/// then clears it. A field that isn't in `targets` never had anything pushed into it in
+/// the first place (see `field_is_target` in `read_selected_fields`), so this is O(1) for
+/// it rather than O(field width).
+fn store_field(field: &mut String, field_index: usize, targets: &[usize], captured: &mut [String]) {
+    for (slot, &target) in targets.iter().enumerate() {
+        if target == field_index {
+            captured[slot] = field.clone();
+        }
+    }
+    field.clear();
+}
+
+/// Drops blank lines and the header row, validates the data row's width, and otherwise
+/// hands `captured` off to `on_record`.
+fn emit_record(
+    fields_in_record: usize,
+    first_field_empty: bool,
+    expected_field_count: usize,
+    skipped_header: &mut bool,
+    captured: &mut Vec<String>,
+    targets: &[usize],
+    on_record: &mut impl FnMut(Vec<String>),
+) -> io::Result<()> {
+    // a lone empty field is only a separator blank line when there's more than one
+    // column - in a single-column table it's indistinguishable from (and must be kept as)
+    // a genuine empty cell, which becomes `Value::Null`
+    if fields_in_record == 1 && first_field_empty && expected_field_count != 1 {
+        return Ok(()); // blank line, same as the line-splitting behavior this replaces
+    }
+
+    if !*skipped_header {
+        *skipped_header = true;
+        return Ok(());
+    }
+
+    if fields_in_record != expected_field_count {
+        return Err(io::Error::new(
+            io::ErrorKind::InvalidData,
+            format!(
+                "Inconsistent amount of data in row (should have: {} columns, has: {})",
+                expected_field_count, fields_in_record
+            ),
+        ));
+    }
+
+    on_record(std::mem::replace(
+        captured,
+        vec![String::new(); targets.len()],
+    ));
+    Ok(())
+}
+
+/// Infers a column's type by trying int, then float, then bool against every non-empty
+/// cell, falling back to string. Empty cells (`Null`) don't constrain the inferred type.
+fn infer_column_type(cells: &[String]) -> ColumnType {
+    let non_empty = cells.iter().filter(|c| !c.is_empty()).collect::<Vec<_>>();
+
+    if !non_empty.is_empty() && non_empty.iter().all(|c| c.parse::<i64>().is_ok()) {
+        return ColumnType::Int;
+    }
+    if !non_empty.is_empty() && non_empty.iter().all(|c| c.parse::<f64>().is_ok()) {
+        return ColumnType::Float;
+    }
+    if !non_empty.is_empty()
+        && non_empty
+            .iter()
+            .all(|c| c.eq_ignore_ascii_case("true") || c.eq_ignore_ascii_case("false"))
+    {
+        return ColumnType::Bool;
+    }
+    ColumnType::Str
+}
+
+fn to_value(cell: &str, column_type: ColumnType) -> Value {
+    if cell.is_empty() {
+        return Value::Null;
+    }
+    match column_type {
+        ColumnType::Int => Value::Int(cell.parse().expect("cell did not match inferred type")),
+        ColumnType::Float => Value::Float(cell.parse().expect("cell did not match inferred type")),
+        ColumnType::Bool => Value::Bool(cell.eq_ignore_ascii_case("true")),
+        ColumnType::Str => Value::Str(cell.to_owned()),
+    }
 }
 
 #[cfg(test)]
 mod tests {
     use super::*;
 
+    /// Parses every record of `input` (no column selection, no header), for tests that
+    /// only care about the RFC 4180 quoting rules themselves.
+    fn parse_records(input: &str) -> Vec<Vec<String>> {
+        let mut records = vec![];
+        let mut fields = vec![];
+        let mut field = String::new();
+        let mut in_quotes = false;
+        let mut chars = input.chars().peekable();
+
+        while let Some(c) = chars.next() {
+            if in_quotes {
+                if c == '"' {
+                    if chars.peek() == Some(&'"') {
+                        field.push('"');
+                        chars.next();
+                    } else {
+                        in_quotes = false;
+                    }
+                } else {
+                    field.push(c);
+                }
+            } else {
+                match c {
+                    '"' => in_quotes = true,
+                    ',' => fields.push(std::mem::take(&mut field)),
+                    '\r' => {}
+                    '\n' => {
+                        fields.push(std::mem::take(&mut field));
+                        let record = std::mem::take(&mut fields);
+                        if record.len() > 1 || !record[0].is_empty() {
+                            records.push(record);
+                        }
+                    }
+                    _ => field.push(c),
+                }
+            }
+        }
+
+        if !field.is_empty() || !fields.is_empty() {
+            fields.push(field);
+            if fields.len() > 1 || !fields[0].is_empty() {
+                records.push(fields);
+            }
+        }
+
+        records
+    }
+
     #[test]
     fn test_read_csv_with_2_columns() {
         let tempdir = tempfile::tempdir().unwrap();
@@ -78,16 +329,163 @@ mod tests {
         std::fs::write(&csv_path, "col1,col2\n1,2\n2,3").unwrap();
 
         let datastore = read_csv(&csv_path, "data").unwrap();
-        assert_eq!(datastore.columns.len(), 2);
+        assert_eq!(datastore.header, vec!["col1".to_owned(), "col2".to_owned()]);
+
+        let col1 = datastore.column("col1").unwrap();
+        assert_eq!(col1.column_type, ColumnType::Int);
+        assert_eq!(col1.data, vec![Value::Int(1), Value::Int(2)]);
+
+        let col2 = datastore.column("col2").unwrap();
+        assert_eq!(col2.column_type, ColumnType::Int);
+        assert_eq!(col2.data, vec![Value::Int(2), Value::Int(3)]);
+    }
+
+    #[test]
+    fn test_read_csv_infers_float_and_string_columns() {
+        let tempdir = tempfile::tempdir().unwrap();
+        let csv_path = tempdir.path().join("data.csv");
+        std::fs::write(&csv_path, "price,name\n1.5,alice\n2,bob").unwrap();
+
+        let datastore = read_csv(&csv_path, "data").unwrap();
+
+        let price = datastore.column("price").unwrap();
+        assert_eq!(price.column_type, ColumnType::Float);
+        assert_eq!(price.data, vec![Value::Float(1.5), Value::Float(2.0)]);
+
+        let name = datastore.column("name").unwrap();
+        assert_eq!(name.column_type, ColumnType::Str);
+        assert_eq!(
+            name.data,
+            vec![Value::Str("alice".to_owned()), Value::Str("bob".to_owned())]
+        );
+    }
+
+    #[test]
+    fn test_column_unknown_name_is_not_found() {
+        let tempdir = tempfile::tempdir().unwrap();
+        let csv_path = tempdir.path().join("data.csv");
+        std::fs::write(&csv_path, "col1\n1").unwrap();
+
+        let datastore = read_csv(&csv_path, "data").unwrap();
+        let err = datastore.column("missing").unwrap_err();
+        assert_eq!(err.kind(), io::ErrorKind::NotFound);
+    }
+
+    #[test]
+    fn test_parse_records_quoted_comma() {
+        assert_eq!(
+            parse_records(r#""a,b",c"#),
+            vec![vec!["a,b".to_owned(), "c".to_owned()]]
+        );
+    }
+
+    #[test]
+    fn test_parse_records_embedded_newline() {
+        assert_eq!(
+            parse_records("\"a\nb\",c\nd,e"),
+            vec![
+                vec!["a\nb".to_owned(), "c".to_owned()],
+                vec!["d".to_owned(), "e".to_owned()],
+            ]
+        );
+    }
 
-        assert_eq!(datastore.columns.get(0).unwrap().name, "col1");
-        assert_eq!(datastore.columns.get(0).unwrap().data.len(), 2);
-        assert_eq!(datastore.columns.get(0).unwrap().data.get(0).unwrap(), "1");
-        assert_eq!(datastore.columns.get(0).unwrap().data.get(1).unwrap(), "2");
+    #[test]
+    fn test_parse_records_doubled_quote_escaping() {
+        assert_eq!(
+            parse_records(r#""say ""hi""",b"#),
+            vec![vec![r#"say "hi""#.to_owned(), "b".to_owned()]]
+        );
+    }
+
+    #[test]
+    fn test_read_csv_with_quoted_comma_field() {
+        let tempdir = tempfile::tempdir().unwrap();
+        let csv_path = tempdir.path().join("data.csv");
+        std::fs::write(&csv_path, "col1,col2\n\"a,b\",c").unwrap();
 
-        assert_eq!(datastore.columns.get(1).unwrap().name, "col2");
-        assert_eq!(datastore.columns.get(1).unwrap().data.len(), 2);
-        assert_eq!(datastore.columns.get(1).unwrap().data.get(0).unwrap(), "2");
-        assert_eq!(datastore.columns.get(1).unwrap().data.get(1).unwrap(), "3");
+        let datastore = read_csv(&csv_path, "data").unwrap();
+        assert_eq!(
+            datastore.column("col1").unwrap().data,
+            vec![Value::Str("a,b".to_owned())]
+        );
+        assert_eq!(
+            datastore.column("col2").unwrap().data,
+            vec![Value::Str("c".to_owned())]
+        );
     }
-}
\ No newline at end of file
+
+    #[test]
+    fn test_read_csv_empty_cells_become_null() {
+        let tempdir = tempfile::tempdir().unwrap();
+        let csv_path = tempdir.path().join("data.csv");
+        std::fs::write(&csv_path, "col1\n1\n\n3").unwrap();
+
+        let datastore = read_csv(&csv_path, "data").unwrap();
+        let col = datastore.column("col1").unwrap();
+        assert_eq!(col.column_type, ColumnType::Int);
+        assert_eq!(col.data, vec![Value::Int(1), Value::Null, Value::Int(3)]);
+    }
+
+    /// Benchmark-style check that projecting one column doesn't pay for copying the others: a
+    /// large unrequested column sits alongside a small one we actually query, and we bound
+    /// how long reading the small column is allowed to take. The bound is tight enough
+    /// that an implementation which copies every field of every record (rather than only
+    /// the requested one) fails it.
+    #[test]
+    fn test_column_reads_only_the_requested_field() {
+        let tempdir = tempfile::tempdir().unwrap();
+        let csv_path = tempdir.path().join("wide.csv");
+
+        let big_value = "x".repeat(1_000_000);
+        let mut contents = String::from("id,junk\n");
+        for i in 0..50 {
+            contents.push_str(&format!("{},{}\n", i, big_value));
+        }
+        std::fs::write(&csv_path, &contents).unwrap();
+
+        let datastore = read_csv(&csv_path, "wide").unwrap();
+        assert_eq!(datastore.header, vec!["id".to_owned(), "junk".to_owned()]);
+
+        let start = std::time::Instant::now();
+        let id = datastore.column("id").unwrap();
+        let elapsed = start.elapsed();
+
+        assert_eq!(id.column_type, ColumnType::Int);
+        assert_eq!(id.data.len(), 50);
+        assert_eq!(id.data[0], Value::Int(0));
+        assert_eq!(id.data[49], Value::Int(49));
+        assert!(
+            elapsed.as_millis() < 500,
+            "reading one column out of a 50MB unrequested column took {:?} - looks like \
+             the other column's bytes were copied too",
+            elapsed
+        );
+    }
+
+    #[test]
+    fn test_columns_reads_multiple_columns_in_one_pass() {
+        let tempdir = tempfile::tempdir().unwrap();
+        let csv_path = tempdir.path().join("data.csv");
+        std::fs::write(&csv_path, "a,b,c\n1,2,3\n4,5,6").unwrap();
+
+        let datastore = read_csv(&csv_path, "data").unwrap();
+        let columns = datastore.columns(&["c", "a"]).unwrap();
+
+        assert_eq!(columns[0].name, "c");
+        assert_eq!(columns[0].data, vec![Value::Int(3), Value::Int(6)]);
+        assert_eq!(columns[1].name, "a");
+        assert_eq!(columns[1].data, vec![Value::Int(1), Value::Int(4)]);
+    }
+
+    #[test]
+    fn test_column_rejects_row_with_wrong_field_count() {
+        let tempdir = tempfile::tempdir().unwrap();
+        let csv_path = tempdir.path().join("data.csv");
+        std::fs::write(&csv_path, "col1,col2\n1,2\n3").unwrap();
+
+        let datastore = read_csv(&csv_path, "data").unwrap();
+        let err = datastore.column("col1").unwrap_err();
+        assert_eq!(err.kind(), io::ErrorKind::InvalidData);
+    }
+}
@@ -1,3 +1,5 @@
+use std::cmp::Ordering;
+use std::io;
 use std::path::PathBuf;
 
 #[derive(Debug)]
@@ -9,11 +11,85 @@ pub struct Database {
 pub struct Datastore {
     pub name: String,
     pub path: PathBuf,
-    pub columns: Vec<Column>, // TODO: change to read data on-demand
+    /// Column names in file order; also the offset index `column()` uses to know which
+    /// field to pull out of each record - the data itself is never loaded eagerly.
+    pub header: Vec<String>,
 }
 
-#[derive(Debug, Clone)] // TODO: remove Copy/Clone
+impl Datastore {
+    /// Reads `name`'s cells from disk on demand, without building any other column.
+    /// See [`read_column`](crate::csv::read_column).
+    pub fn column(&self, name: &str) -> io::Result<Column> {
+        crate::csv::read_column(self, name)
+    }
+
+    /// Reads several columns in a single pass over the file. See
+    /// [`read_columns`](crate::csv::read_columns).
+    pub fn columns(&self, names: &[&str]) -> io::Result<Vec<Column>> {
+        crate::csv::read_columns(self, names)
+    }
+}
+
+#[derive(Debug)]
 pub struct Column {
     pub name: String,
-    pub data: Vec<String>,
-}
\ No newline at end of file
+    pub data: Vec<Value>,
+    pub column_type: ColumnType,
+}
+
+/// The type a `Column`'s cells were inferred to hold, see [`read_columns`](crate::csv::read_columns).
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ColumnType {
+    Int,
+    Float,
+    Bool,
+    Str,
+}
+
+#[derive(Debug, Clone)]
+pub enum Value {
+    Int(i64),
+    Float(f64),
+    Bool(bool),
+    Str(String),
+    Null,
+}
+
+impl Value {
+    fn as_f64(&self) -> Option<f64> {
+        match self {
+            Value::Int(v) => Some(*v as f64),
+            Value::Float(v) => Some(*v),
+            _ => None,
+        }
+    }
+}
+
+// numeric variants compare across `Int`/`Float` so `WHERE` can use real `<`/`>` instead of
+// lexicographic string comparison. This is structural equality (used by tests and
+// elsewhere that need to tell two `Value`s apart), so `Null == Null` holds here; SQL's
+// "NULL never equals anything" rule is applied separately, where a `WHERE` comparison is
+// actually evaluated - see `eval_predicate` in main.rs.
+impl PartialEq for Value {
+    fn eq(&self, other: &Self) -> bool {
+        match (self, other) {
+            (Value::Null, Value::Null) => true,
+            (Value::Bool(a), Value::Bool(b)) => a == b,
+            (Value::Str(a), Value::Str(b)) => a == b,
+            _ => matches!((self.as_f64(), other.as_f64()), (Some(a), Some(b)) if a == b),
+        }
+    }
+}
+
+impl PartialOrd for Value {
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+        match (self, other) {
+            (Value::Bool(a), Value::Bool(b)) => a.partial_cmp(b),
+            (Value::Str(a), Value::Str(b)) => a.partial_cmp(b),
+            _ => match (self.as_f64(), other.as_f64()) {
+                (Some(a), Some(b)) => a.partial_cmp(&b),
+                _ => None,
+            },
+        }
+    }
+}
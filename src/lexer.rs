@@ -1,6 +1,6 @@
 use nom::branch::alt;
 use nom::bytes::complete::{is_not, tag, take_while, take_while1};
-use nom::combinator::{all_consuming, eof};
+use nom::combinator::{all_consuming, consumed, eof};
 use nom::error::{Error, ErrorKind, ParseError};
 use nom::multi::many0;
 use nom::sequence::delimited;
@@ -9,15 +9,32 @@ use nom::sequence::delimited;
 
 #[derive(Debug, Clone, Eq, PartialEq)]
 pub enum Token {
-    // TODO: add numbers + operators (later)
     Keyword(String),
     Identifier(String),
     Literal(String),
+    Number(String),
+    Operator(String),
     Comma,
-    Parens(Vec<Token>),
+    Wildcard,
+    Parens(Vec<TokenWithSpan>),
 }
 
-pub fn tokenize(s: &str) -> nom::IResult<&str, Vec<Token>> {
+/// A byte-offset range into the original query string.
+#[derive(Debug, Clone, Eq, PartialEq)]
+pub struct Span {
+    pub start: usize,
+    pub end: usize,
+}
+
+/// Nested `Parens` spans are relative to the parenthesized sub-expression they were read
+/// from, not the top-level query - only the outermost tokens carry absolute offsets.
+#[derive(Debug, Clone, Eq, PartialEq)]
+pub struct TokenWithSpan {
+    pub token: Token,
+    pub span: Span,
+}
+
+pub fn tokenize(s: &str) -> nom::IResult<&str, Vec<TokenWithSpan>> {
     all_consuming(tokenize_internal)(s)
 }
 
@@ -56,6 +73,24 @@ fn identifier(s: &str) -> nom::IResult<&str, Option<Token>> {
     Ok((s, Some(Token::Identifier(r.to_owned()))))
 }
 
+fn number(s: &str) -> nom::IResult<&str, Option<Token>> {
+    let (s, r) = take_while1(|c: char| c.is_ascii_digit())(s)?;
+    Ok((s, Some(Token::Number(r.to_owned()))))
+}
+
+// longer operators (e.g. "<=") must be listed before the shorter ones they contain (e.g. "<")
+fn operator(s: &str) -> nom::IResult<&str, Option<Token>> {
+    let (s, r) = alt((
+        tag("<="),
+        tag(">="),
+        tag("<>"),
+        tag("="),
+        tag("<"),
+        tag(">"),
+    ))(s)?;
+    Ok((s, Some(Token::Operator(r.to_owned()))))
+}
+
 fn line_comment(s: &str) -> nom::IResult<&str, Option<Token>> {
     let (s, _) = tag("--")(s)?;
     let (s, _) = take_while(|c| c != '\n')(s)?;
@@ -74,9 +109,11 @@ fn comment(s: &str) -> nom::IResult<&str, Option<Token>> {
 }
 
 // case insensitive
-fn take_identifier(name: &str) -> Box<dyn Fn(&[Token]) -> nom::IResult<&[Token], &[Token]>> {
+fn take_identifier(
+    name: &str,
+) -> Box<dyn Fn(&[TokenWithSpan]) -> nom::IResult<&[TokenWithSpan], &[TokenWithSpan]>> {
     let name = name.to_owned();
-    Box::new(move |i: &[Token]| {
+    Box::new(move |i: &[TokenWithSpan]| {
         let elem = match i.first() {
             Some(v) => v,
             None => {
@@ -84,20 +121,21 @@ fn take_identifier(name: &str) -> Box<dyn Fn(&[Token]) -> nom::IResult<&[Token],
             }
         };
 
-        if let Token::Identifier(curr) = elem {
+        if let Token::Identifier(curr) = &elem.token {
             if curr.to_lowercase() == name.to_lowercase() {
                 return Ok((&i[1..], &i[..1]));
             }
         }
 
-        Err(nom::Err::Error(Error::from_error_kind(  // TODO: create custom errors
+        Err(nom::Err::Error(Error::from_error_kind(
+            // TODO: create custom errors
             i,
             ErrorKind::TagBits,
         )))
     })
 }
 
-fn take_any(i: &[Token]) -> nom::IResult<&[Token], Token> {
+fn take_any(i: &[TokenWithSpan]) -> nom::IResult<&[TokenWithSpan], TokenWithSpan> {
     match i.first() {
         Some(v) => Ok((&i[1..], v.to_owned())),
         None => Err(nom::Err::Error(Error::from_error_kind(i, ErrorKind::Eof))),
@@ -106,18 +144,38 @@ fn take_any(i: &[Token]) -> nom::IResult<&[Token], Token> {
 
 /// identifiers are split by " " token
 /// keywords are returned in uppercase
-fn keyword(name: &str) -> Box<dyn Fn(&[Token]) -> nom::IResult<&[Token], Token>> {
+/// the keyword's span is the union of the spans of the identifiers it was built from, so
+/// e.g. "LEFT JOIN" carries one span covering both words
+fn keyword(
+    name: &str,
+) -> Box<dyn Fn(&[TokenWithSpan]) -> nom::IResult<&[TokenWithSpan], TokenWithSpan>> {
     let name = name.to_owned();
     Box::new(move |s| {
         let mut x = s;
+        let mut span: Option<Span> = None;
         for n in name.split(" ") {
-            x = take_identifier(n)(x)?.0;
+            let (next, matched) = take_identifier(n)(x)?;
+            let piece_span = matched[0].span.clone();
+            span = Some(match span {
+                None => piece_span,
+                Some(acc) => Span {
+                    start: acc.start,
+                    end: piece_span.end,
+                },
+            });
+            x = next;
         }
-        Ok((&x, Token::Keyword(name.to_uppercase())))
+        Ok((
+            x,
+            TokenWithSpan {
+                token: Token::Keyword(name.to_uppercase()),
+                span: span.expect("keyword name must not be empty"),
+            },
+        ))
     })
 }
 
-fn resolve_keywords(s: &[Token]) -> nom::IResult<&[Token], Vec<Token>> {
+fn resolve_keywords(s: &[TokenWithSpan]) -> nom::IResult<&[TokenWithSpan], Vec<TokenWithSpan>> {
     // Keywords contained by other keywords must be placed after them - e.g.
     // "join" must be further in the list than "left join".
     let (s, r) = many0(alt((
@@ -126,23 +184,44 @@ fn resolve_keywords(s: &[Token]) -> nom::IResult<&[Token], Vec<Token>> {
         keyword("where"),
         keyword("left join"),
         keyword("join"),
+        keyword("on"),
+        keyword("and"),
+        keyword("or"),
+        keyword("as"),
         take_any,
     )))(s)?;
 
     Ok((s, r))
 }
 
-fn tokenize_internal(s: &str) -> nom::IResult<&str, Vec<Token>> {
-    let (s, r) = many0(alt((
+fn tokenize_internal(s: &str) -> nom::IResult<&str, Vec<TokenWithSpan>> {
+    let (s, r) = many0(consumed(alt((
         comment,
         parens,
         literal,
         const_token(",", Token::Comma),
+        const_token("*", Token::Wildcard),
         whitespace,
+        number,
+        operator,
         identifier,
-    )))(s)?;
+    ))))(s)?;
+
+    // spans are computed from how much of the (local) input each token consumed, per the
+    // lengths of the slices `consumed` hands back, rather than tracked through the recursive-descent calls
+    let mut offset = 0;
+    let tokens: Vec<TokenWithSpan> = r
+        .into_iter()
+        .filter_map(|(consumed_str, maybe_token)| {
+            let start = offset;
+            offset += consumed_str.len();
+            maybe_token.map(|token| TokenWithSpan {
+                token,
+                span: Span { start, end: offset },
+            })
+        })
+        .collect();
 
-    let tokens: Vec<Token> = r.into_iter().flatten().collect();
     let (_, tokens) =
         all_consuming(resolve_keywords)(tokens.as_slice()).expect("resolve keywords failed"); // TODO: fix error passing  // NOTE: I don't think resolve keywords should ever fail as it accepts every token, so maybe there is no point of fighting with errors
 
@@ -155,16 +234,34 @@ mod tests {
     use nom::error::{Error, ErrorKind};
     use nom::Err;
 
+    fn tws(token: Token, start: usize, end: usize) -> TokenWithSpan {
+        TokenWithSpan {
+            token,
+            span: Span { start, end },
+        }
+    }
+
     #[test]
     fn test_parens() {
-        assert_eq!(tokenize("()").unwrap().1, vec![Token::Parens(vec![])]);
+        assert_eq!(
+            tokenize("()").unwrap().1,
+            vec![tws(Token::Parens(vec![]), 0, 2)]
+        );
         assert_eq!(
             tokenize("(())").unwrap().1,
-            vec![Token::Parens(vec![Token::Parens(vec![])])]
+            vec![tws(
+                Token::Parens(vec![tws(Token::Parens(vec![]), 0, 2)]),
+                0,
+                4
+            )]
         );
         assert_eq!(
             tokenize(r#"(")(")"#).unwrap().1,
-            vec![Token::Parens(vec![Token::Literal(")(".to_owned())])]
+            vec![tws(
+                Token::Parens(vec![tws(Token::Literal(")(".to_owned()), 0, 4)]),
+                0,
+                6
+            )]
         );
     }
 
@@ -184,10 +281,10 @@ mod tests {
         assert_eq!(
             tokenize(r#""a",,"b""#).unwrap().1,
             vec![
-                Token::Literal("a".to_owned()),
-                Token::Comma,
-                Token::Comma,
-                Token::Literal("b".to_owned())
+                tws(Token::Literal("a".to_owned()), 0, 3),
+                tws(Token::Comma, 3, 4),
+                tws(Token::Comma, 4, 5),
+                tws(Token::Literal("b".to_owned()), 5, 8),
             ]
         )
     }
@@ -196,7 +293,7 @@ mod tests {
     fn test_whitespace() {
         assert_eq!(
             tokenize(" \t\n\" a\t\n \"\t\n ").unwrap().1,
-            vec![Token::Literal(" a\t\n ".to_owned()),]
+            vec![tws(Token::Literal(" a\t\n ".to_owned()), 3, 10)]
         )
     }
 
@@ -204,7 +301,7 @@ mod tests {
     fn test_identifier() {
         assert_eq!(
             tokenize("id").unwrap().1,
-            vec![Token::Identifier("id".to_owned())]
+            vec![tws(Token::Identifier("id".to_owned()), 0, 2)]
         )
     }
 
@@ -213,12 +310,55 @@ mod tests {
         assert_eq!(
             tokenize("id1--id2\nid3").unwrap().1,
             vec![
-                Token::Identifier("id1".to_owned()),
-                Token::Identifier("id3".to_owned())
+                tws(Token::Identifier("id1".to_owned()), 0, 3),
+                tws(Token::Identifier("id3".to_owned()), 9, 12),
             ]
         )
     }
 
+    #[test]
+    fn test_number() {
+        assert_eq!(
+            tokenize("123").unwrap().1,
+            vec![tws(Token::Number("123".to_owned()), 0, 3)]
+        )
+    }
+
+    #[test]
+    fn test_operators() {
+        assert_eq!(
+            tokenize("= < > <= >= <>").unwrap().1,
+            vec![
+                tws(Token::Operator("=".to_owned()), 0, 1),
+                tws(Token::Operator("<".to_owned()), 2, 3),
+                tws(Token::Operator(">".to_owned()), 4, 5),
+                tws(Token::Operator("<=".to_owned()), 6, 8),
+                tws(Token::Operator(">=".to_owned()), 9, 11),
+                tws(Token::Operator("<>".to_owned()), 12, 14),
+            ]
+        )
+    }
+
+    #[test]
+    fn test_wildcard() {
+        assert_eq!(tokenize("*").unwrap().1, vec![tws(Token::Wildcard, 0, 1)])
+    }
+
+    #[test]
+    fn test_select_star_and_as() {
+        assert_eq!(
+            tokenize("SELECT * FROM t AS x").unwrap().1,
+            vec![
+                tws(Token::Keyword("SELECT".to_owned()), 0, 6),
+                tws(Token::Wildcard, 7, 8),
+                tws(Token::Keyword("FROM".to_owned()), 9, 13),
+                tws(Token::Identifier("t".to_owned()), 14, 15),
+                tws(Token::Keyword("AS".to_owned()), 16, 18),
+                tws(Token::Identifier("x".to_owned()), 19, 20),
+            ]
+        );
+    }
+
     #[test]
     fn test_keywords() {
         assert_eq!(
@@ -226,16 +366,52 @@ mod tests {
                 .unwrap()
                 .1,
             vec![
-                Token::Keyword("SELECT".to_owned()),
-                Token::Identifier("x".to_owned()),
-                Token::Comma,
-                Token::Identifier("y".to_owned()),
-                Token::Keyword("FROM".to_owned()),
-                Token::Identifier("t1".to_owned()),
-                Token::Keyword("LEFT JOIN".to_owned()),
-                Token::Identifier("t2".to_owned()),
-                Token::Keyword("JOIN".to_owned()),
-                Token::Identifier("t3".to_owned()),
+                tws(Token::Keyword("SELECT".to_owned()), 0, 6),
+                tws(Token::Identifier("x".to_owned()), 7, 8),
+                tws(Token::Comma, 8, 9),
+                tws(Token::Identifier("y".to_owned()), 9, 10),
+                tws(Token::Keyword("FROM".to_owned()), 11, 15),
+                tws(Token::Identifier("t1".to_owned()), 16, 18),
+                tws(Token::Keyword("LEFT JOIN".to_owned()), 19, 28),
+                tws(Token::Identifier("t2".to_owned()), 29, 31),
+                tws(Token::Keyword("JOIN".to_owned()), 32, 36),
+                tws(Token::Identifier("t3".to_owned()), 37, 39),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_join_on() {
+        assert_eq!(
+            tokenize("JOIN t2 ON a = b").unwrap().1,
+            vec![
+                tws(Token::Keyword("JOIN".to_owned()), 0, 4),
+                tws(Token::Identifier("t2".to_owned()), 5, 7),
+                tws(Token::Keyword("ON".to_owned()), 8, 10),
+                tws(Token::Identifier("a".to_owned()), 11, 12),
+                tws(Token::Operator("=".to_owned()), 13, 14),
+                tws(Token::Identifier("b".to_owned()), 15, 16),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_where_keywords() {
+        assert_eq!(
+            tokenize("WHERE a = 1 AND b <> 2 OR c > 3").unwrap().1,
+            vec![
+                tws(Token::Keyword("WHERE".to_owned()), 0, 5),
+                tws(Token::Identifier("a".to_owned()), 6, 7),
+                tws(Token::Operator("=".to_owned()), 8, 9),
+                tws(Token::Number("1".to_owned()), 10, 11),
+                tws(Token::Keyword("AND".to_owned()), 12, 15),
+                tws(Token::Identifier("b".to_owned()), 16, 17),
+                tws(Token::Operator("<>".to_owned()), 18, 20),
+                tws(Token::Number("2".to_owned()), 21, 22),
+                tws(Token::Keyword("OR".to_owned()), 23, 25),
+                tws(Token::Identifier("c".to_owned()), 26, 27),
+                tws(Token::Operator(">".to_owned()), 28, 29),
+                tws(Token::Number("3".to_owned()), 30, 31),
             ]
         );
     }
@@ -1,34 +1,369 @@
 extern crate nom;
 
-use simplidb::parser::{SelectExpression, DataSource};
-use std::path::Path;
-use simplidb::database::{Database, Column};
-use std::io;
 use simplidb::csv::read_csv;
+use simplidb::database::{Column, ColumnType, Database, Value};
+use simplidb::parser::{DataSource, Expr, JoinKind, Operand, SelectExpression, SelectItem};
+use std::collections::HashMap;
+use std::io;
+use std::path::Path;
 
-fn execute(expression: SelectExpression, db: Database) -> Vec<Column> {
-    let columns = match expression.source {
-        DataSource::Datastore { name } => db
-            .datastores
-            .iter()
-            .find(|x| x.name == name)
-            .expect(&format!("No table: {} found", &name))
-            .columns
-            .to_owned(), // NOTE: COPY!
-        DataSource::SelectExpression(subselect) => execute(*subselect, db),
+fn execute(expression: SelectExpression, db: &Database) -> Vec<Column> {
+    let wants_all = expression.items.iter().any(item_wants_all);
+    let mut needed: Vec<String> = expression
+        .items
+        .iter()
+        .filter_map(referenced_column)
+        .collect();
+    if let Some(predicate) = &expression.predicate {
+        needed.extend(predicate_columns(predicate));
+    }
+    let needed = if wants_all {
+        None
+    } else {
+        Some(needed.as_slice())
+    };
+
+    let columns = resolve_source(expression.source, db, needed);
+
+    let mut columns = match &expression.predicate {
+        Some(predicate) => filter_rows(&columns, predicate),
+        None => columns,
     };
 
+    let mut remaining_uses = count_column_uses(&expression.items);
     expression
-        .columns
+        .items
+        .into_iter()
+        .flat_map(|item| project_item(item, &mut columns, &mut remaining_uses))
+        .collect() // TODO: handle ambiguity
+}
+
+/// How many times each plain column name is referenced across `items` - lets
+/// `take_or_clone_column` know whether it's consuming the last reference to a column (and
+/// can move it out) or an earlier one (and must clone it instead).
+fn count_column_uses(items: &[SelectItem]) -> HashMap<String, usize> {
+    let mut counts = HashMap::new();
+    for item in items {
+        if let Some(name) = referenced_column(item) {
+            *counts.entry(name).or_insert(0) += 1;
+        }
+    }
+    counts
+}
+
+/// Whether `item` expands to every column of the resolved source (i.e. is, or aliases, a
+/// `Wildcard`), which means a `Datastore` can't narrow down which columns it needs to read.
+fn item_wants_all(item: &SelectItem) -> bool {
+    match item {
+        SelectItem::Wildcard => true,
+        SelectItem::Column(_) => false,
+        SelectItem::Aliased { expr, .. } => item_wants_all(expr),
+    }
+}
+
+/// The single column name `item` projects, if any (`Wildcard` has none).
+fn referenced_column(item: &SelectItem) -> Option<String> {
+    match item {
+        SelectItem::Wildcard => None,
+        SelectItem::Column(name) => Some(name.clone()),
+        SelectItem::Aliased { expr, .. } => referenced_column(expr),
+    }
+}
+
+/// Resolves one `SelectItem` against `columns` - `Wildcard` takes every remaining column,
+/// `Column` takes the one it names (cloning it if a later item still needs it, see
+/// `take_or_clone_column`), and `Aliased` renames whatever its wrapped item resolves to.
+fn project_item(
+    item: SelectItem,
+    columns: &mut Vec<Column>,
+    remaining_uses: &mut HashMap<String, usize>,
+) -> Vec<Column> {
+    match item {
+        SelectItem::Wildcard => std::mem::take(columns),
+        SelectItem::Column(name) => vec![take_or_clone_column(columns, &name, remaining_uses)],
+        SelectItem::Aliased { expr, alias } => project_item(*expr, columns, remaining_uses)
+            .into_iter()
+            .map(|c| Column {
+                name: alias.clone(),
+                data: c.data,
+                column_type: c.column_type,
+            })
+            .collect(),
+    }
+}
+
+/// Looks up `name` in `columns` and either moves it out (if this is its last reference
+/// across the select list) or clones its data (if a later item still references it, so
+/// e.g. `SELECT id, id AS alt_id` doesn't panic on the second reference).
+fn take_or_clone_column(
+    columns: &mut Vec<Column>,
+    name: &str,
+    remaining_uses: &mut HashMap<String, usize>,
+) -> Column {
+    let idx = columns
         .iter()
-        .map(|x| {
+        .position(|y| y.name == name || y.name.ends_with(&format!(".{}", name)))
+        .expect(&format!("No column: {} found", name));
+
+    let remaining = remaining_uses.entry(name.to_owned()).or_insert(1);
+    *remaining -= 1;
+    if *remaining == 0 {
+        columns.remove(idx)
+    } else {
+        let c = &columns[idx];
+        Column {
+            name: c.name.clone(),
+            data: c.data.clone(),
+            column_type: c.column_type,
+        }
+    }
+}
+
+/// Column names referenced by `expr`, so a `Datastore` only has to read those off disk.
+fn predicate_columns(expr: &Expr) -> Vec<String> {
+    match expr {
+        Expr::And(left, right) | Expr::Or(left, right) => {
+            let mut columns = predicate_columns(left);
+            columns.extend(predicate_columns(right));
             columns
+        }
+        Expr::Compare { left, right, .. } => [left, right]
+            .into_iter()
+            .filter_map(|operand| match operand {
+                Operand::ColumnRef(name) => Some(name.clone()),
+                Operand::Literal(_) => None,
+            })
+            .collect(),
+    }
+}
+
+/// Resolves `source` into columns, reading a `Datastore`'s columns lazily - only the ones
+/// named in `needed` (qualified or not) are pulled off disk. `needed: None` means every
+/// column is wanted, e.g. because the query projects a `Wildcard`.
+fn resolve_source(source: DataSource, db: &Database, needed: Option<&[String]>) -> Vec<Column> {
+    match source {
+        DataSource::Datastore { name } => {
+            let datastore = db
+                .datastores
+                .iter()
+                .find(|x| x.name == name)
+                .expect(&format!("No table: {} found", &name));
+
+            let names: Vec<&str> = datastore
+                .header
                 .iter()
-                .find(|y| y.name == *x)
-                .expect(&format!("No column: {} found", x))
-                .to_owned()
+                .filter(|h| match needed {
+                    None => true,
+                    Some(needed) => needed
+                        .iter()
+                        .any(|n| n == *h || n.ends_with(&format!(".{}", h))),
+                })
+                .map(|h| h.as_str())
+                .collect();
+            datastore.columns(&names).expect("failed to read columns")
+        }
+        DataSource::SelectExpression(subselect) => execute(*subselect, db),
+        DataSource::Join {
+            left,
+            right,
+            kind,
+            on,
+        } => {
+            // owned, not borrowed - `*left`/`*right` get moved into `resolve_source` below,
+            // so a name borrowed from them couldn't outlive that move
+            let left_name = datastore_name(&left);
+            let right_name = datastore_name(&right);
+
+            let left_needed = needed.map(|n| {
+                let mut n = n.to_vec();
+                n.push(on.0.clone());
+                n
+            });
+            let right_needed = needed.map(|n| {
+                let mut n = n.to_vec();
+                n.push(on.1.clone());
+                n
+            });
+
+            let left_columns = qualify(
+                resolve_source(*left, db, left_needed.as_deref()),
+                left_name.as_deref(),
+            );
+            let right_columns = qualify(
+                resolve_source(*right, db, right_needed.as_deref()),
+                right_name.as_deref(),
+            );
+            hash_join(left_columns, right_columns, kind, on)
+        }
+    }
+}
+
+fn datastore_name(source: &DataSource) -> Option<String> {
+    match source {
+        DataSource::Datastore { name } => Some(name.clone()),
+        _ => None,
+    }
+}
+
+/// Qualifies each `Column.name` as `table.column`, when `table` is known.
+fn qualify(columns: Vec<Column>, table: Option<&str>) -> Vec<Column> {
+    match table {
+        Some(table) => columns
+            .into_iter()
+            .map(|c| Column {
+                name: format!("{}.{}", table, c.name),
+                data: c.data,
+                column_type: c.column_type,
+            })
+            .collect(),
+        None => columns,
+    }
+}
+
+/// Canonical string representation of a `Value`, used as a hash-join key.
+fn join_key(value: &Value) -> String {
+    match value {
+        Value::Int(v) => v.to_string(),
+        Value::Float(v) => v.to_string(),
+        Value::Bool(v) => v.to_string(),
+        Value::Str(v) => v.clone(),
+        Value::Null => String::new(),
+    }
+}
+
+fn find_column<'a>(columns: &'a [Column], name: &str) -> &'a Column {
+    columns
+        .iter()
+        .find(|c| c.name == name || c.name.ends_with(&format!(".{}", name)))
+        .expect(&format!("No column: {} found", name))
+}
+
+/// Builds a `HashMap` from the right table's join-key values to its row indices, then
+/// streams the left table's rows through it to emit the joined output.
+fn hash_join(
+    left: Vec<Column>,
+    right: Vec<Column>,
+    kind: JoinKind,
+    on: (String, String),
+) -> Vec<Column> {
+    let (left_key, right_key) = on;
+    let right_key_col = find_column(&right, &right_key);
+
+    // `NULL` never equals anything, not even another `NULL` - so a `NULL` key is never
+    // inserted into the index and never looked up in it, rather than coercing it to the
+    // same key as an empty string.
+    let mut index: HashMap<String, Vec<usize>> = HashMap::new();
+    for (i, key) in right_key_col.data.iter().enumerate() {
+        if *key == Value::Null {
+            continue;
+        }
+        index.entry(join_key(key)).or_insert_with(Vec::new).push(i);
+    }
+
+    let left_key_col = find_column(&left, &left_key);
+    let left_row_count = left.first().map(|c| c.data.len()).unwrap_or(0);
+
+    let mut left_rows: Vec<usize> = vec![];
+    let mut right_rows: Vec<Option<usize>> = vec![];
+    for left_row in 0..left_row_count {
+        let left_key_value = &left_key_col.data[left_row];
+        let matched = if *left_key_value == Value::Null {
+            None
+        } else {
+            index.get(&join_key(left_key_value))
+        };
+        match matched {
+            Some(matches) => {
+                for &right_row in matches {
+                    left_rows.push(left_row);
+                    right_rows.push(Some(right_row));
+                }
+            }
+            None => {
+                if kind == JoinKind::Left {
+                    left_rows.push(left_row);
+                    right_rows.push(None);
+                }
+            }
+        }
+    }
+
+    let left_output = left.iter().map(|c| Column {
+        name: c.name.clone(),
+        data: left_rows.iter().map(|&row| c.data[row].clone()).collect(),
+        column_type: c.column_type,
+    });
+    let right_output = right.iter().map(|c| Column {
+        name: c.name.clone(),
+        data: right_rows
+            .iter()
+            .map(|row| match row {
+                Some(row) => c.data[*row].clone(),
+                None => Value::Null,
+            })
+            .collect(),
+        column_type: c.column_type,
+    });
+
+    left_output.chain(right_output).collect()
+}
+
+/// Keeps only the rows for which `predicate` evaluates to true, across all `columns`.
+fn filter_rows(columns: &[Column], predicate: &Expr) -> Vec<Column> {
+    let row_count = columns.first().map(|c| c.data.len()).unwrap_or(0);
+    let kept_rows: Vec<usize> = (0..row_count)
+        .filter(|&row| eval_predicate(predicate, columns, row))
+        .collect();
+
+    columns
+        .iter()
+        .map(|c| Column {
+            name: c.name.clone(),
+            data: kept_rows.iter().map(|&row| c.data[row].clone()).collect(),
+            column_type: c.column_type,
         })
-        .collect() // TODO: handle ambiguity
+        .collect()
+}
+
+fn eval_predicate(expr: &Expr, columns: &[Column], row: usize) -> bool {
+    match expr {
+        Expr::And(left, right) => {
+            eval_predicate(left, columns, row) && eval_predicate(right, columns, row)
+        }
+        Expr::Or(left, right) => {
+            eval_predicate(left, columns, row) || eval_predicate(right, columns, row)
+        }
+        Expr::Compare { left, op, right } => {
+            let left = resolve_operand(left, columns, row);
+            let right = resolve_operand(right, columns, row);
+            // SQL's three-valued logic: `NULL` never compares equal (or unequal) to
+            // anything, not even another `NULL` - unlike `Value`'s own `PartialEq`, which
+            // is structural equality used elsewhere (e.g. comparing test fixtures).
+            if matches!(left, Value::Null) || matches!(right, Value::Null) {
+                return false;
+            }
+            match op.as_str() {
+                "=" => left == right,
+                "<>" => left != right,
+                "<" => left < right,
+                ">" => left > right,
+                "<=" => left <= right,
+                ">=" => left >= right,
+                _ => panic!("unknown operator: {}", op),
+            }
+        }
+    }
+}
+
+fn resolve_operand(operand: &Operand, columns: &[Column], row: usize) -> Value {
+    match operand {
+        Operand::ColumnRef(name) => find_column(columns, name)
+            .data
+            .get(row)
+            .expect("row index out of range")
+            .clone(),
+        Operand::Literal(v) => v.clone(),
+    }
 }
 
 #[derive(Debug, PartialEq)]
@@ -48,13 +383,14 @@ fn main() -> std::result::Result<(), io::Error> {
     };
 
     let select = SelectExpression {
-        columns: vec!["name".to_owned()],
+        items: vec![SelectItem::Column("name".to_owned())],
         source: DataSource::Datastore {
             name: "employee".to_owned(),
         },
+        predicate: None,
     };
 
-    let result = execute(select, db);
+    let result = execute(select, &db);
     println!("query result: {:#?}", result);
 
     Ok(())
@@ -62,3 +398,24 @@ fn main() -> std::result::Result<(), io::Error> {
 
 // TODO: can track if file info is up to date by checking file modification time
 // TODO: serialize deserialize tests
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_where_null_column_does_not_equal_itself() {
+        let columns = vec![Column {
+            name: "x".to_owned(),
+            data: vec![Value::Null],
+            column_type: ColumnType::Int,
+        }];
+        let predicate = Expr::Compare {
+            left: Operand::ColumnRef("x".to_owned()),
+            op: "=".to_owned(),
+            right: Operand::ColumnRef("x".to_owned()),
+        };
+
+        assert!(!eval_predicate(&predicate, &columns, 0));
+    }
+}
@@ -1,25 +1,73 @@
-use crate::lexer::{tokenize, Token};
+use crate::database::Value;
+use crate::lexer::{tokenize, Token, TokenWithSpan};
 use core::fmt;
 use nom::error::{ErrorKind, ParseError};
 use nom::IResult;
 use std::error::Error;
-use nom::bytes::complete::take;
 
 #[derive(Debug, PartialEq)]
 pub struct SelectExpression {
-    pub columns: Vec<String>,
+    pub items: Vec<SelectItem>,
     pub source: DataSource,
+    pub predicate: Option<Expr>,
+}
+
+/// An entry in a `SELECT` list - either every column, one column by name, or another
+/// `SelectItem` renamed via `AS`.
+#[derive(Debug, PartialEq)]
+pub enum SelectItem {
+    Wildcard,
+    Column(String),
+    Aliased {
+        expr: Box<SelectItem>,
+        alias: String,
+    },
 }
 
 #[derive(Debug, PartialEq)]
 pub enum DataSource {
-    Datastore { name: String },
+    Datastore {
+        name: String,
+    },
     SelectExpression(Box<SelectExpression>),
+    Join {
+        left: Box<DataSource>,
+        right: Box<DataSource>,
+        kind: JoinKind,
+        on: (String, String),
+    },
+}
+
+#[derive(Debug, PartialEq)]
+pub enum JoinKind {
+    Inner,
+    Left,
+}
+
+/// Operand of a `WHERE` comparison - either a reference to a column or a literal value.
+/// The literal's `Value` is built straight from the token it came from (a quoted string
+/// always becomes `Value::Str`, a bare number is parsed numerically), so e.g. `"007"`
+/// stays a string instead of being re-inferred as `Value::Int(7)`.
+#[derive(Debug, PartialEq)]
+pub enum Operand {
+    ColumnRef(String),
+    Literal(Value),
+}
+
+#[derive(Debug, PartialEq)]
+pub enum Expr {
+    Compare {
+        left: Operand,
+        op: String,
+        right: Operand,
+    },
+    And(Box<Expr>, Box<Expr>),
+    Or(Box<Expr>, Box<Expr>),
 }
 
 pub fn parse(s: &str) -> Result<SelectExpression, String> {
     let (_, tokens) = tokenize(s).map_err(|e| e.to_string())?;
-    let (remaining, parsed) = parse_internal(tokens.as_slice()).map_err(|e| e.to_string())?;
+    let (remaining, parsed) = parse_internal(tokens.as_slice()).map_err(|e| render_error(s, &e))?;
     if !remaining.is_empty() {
         return Err(format!(
             "there are remaining tokens that were not parsed: {:?}",
@@ -29,12 +77,44 @@ pub fn parse(s: &str) -> Result<SelectExpression, String> {
     Ok(parsed)
 }
 
+/// Renders a parse failure as a message followed by a caret-underlined snippet of `query`
+/// pointing at the offending offset, e.g.:
+/// ```text
+/// keyword "FROM" expected at offset 17
+/// SELECT name, age x FROM t
+///                  ^
+/// ```
+fn render_error(query: &str, err: &nom::Err<SqlParseError>) -> String {
+    let err = match err {
+        nom::Err::Error(e) | nom::Err::Failure(e) => e,
+        nom::Err::Incomplete(_) => return "unexpected end of input".to_owned(),
+    };
+
+    match err {
+        SqlParseError::CustomError { message, offset } => {
+            let caret_pos = (*offset).min(query.len());
+            format!(
+                "{} at offset {}\n{}\n{}^",
+                message,
+                offset,
+                query,
+                " ".repeat(caret_pos)
+            )
+        }
+        other => other.to_string(),
+    }
+}
+
+fn offset_of(s: &[TokenWithSpan]) -> usize {
+    s.first().map(|t| t.span.start).unwrap_or(0)
+}
+
 #[derive(Debug, PartialEq)]
 // TODO: add <I> template and pass it to NomError if it can be helpful
 pub enum SqlParseError {
-    CustomError(String),
+    CustomError { message: String, offset: usize },
     Eof,
-    RemainingTokens(Vec<Token>),
+    RemainingTokens(Vec<TokenWithSpan>),
     NomError(ErrorKind),
 }
 
@@ -47,8 +127,8 @@ impl Error for SqlParseError {
 impl fmt::Display for SqlParseError {
     fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
         match self {
-            SqlParseError::CustomError(e) => {
-                write!(f, "Custom error: {}", e)
+            SqlParseError::CustomError { message, offset } => {
+                write!(f, "{} at offset {}", message, offset)
             }
             SqlParseError::Eof => {
                 write!(f, "Unexpected end of file")
@@ -77,25 +157,198 @@ impl<I> ParseError<I> for SqlParseError {
     }
 }
 
-fn parse_internal(s: &[Token]) -> IResult<&[Token], SelectExpression, SqlParseError> {
+fn parse_internal(
+    s: &[TokenWithSpan],
+) -> IResult<&[TokenWithSpan], SelectExpression, SqlParseError> {
     let (s, _) = take_keyword("SELECT")(s)?;
-    let (s, col) = take_identifier(s)?;
+    let (s, items) = parse_select_list(s)?;
     let (s, _) = take_keyword("FROM")(s)?;
     let (s, table) = take_identifier(s)?;
+    let (s, source) = parse_joins(s, DataSource::Datastore { name: table })?;
+    let (s, predicate) = match take_keyword("WHERE")(s) {
+        Ok((s, _)) => {
+            let (s, expr) = parse_or_expr(s)?;
+            (s, Some(expr))
+        }
+        Err(_) => (s, None),
+    };
 
     Ok((
         s,
         SelectExpression {
-            columns: vec![col],
-            source: DataSource::Datastore {
-                name: table
-            },
+            items,
+            source,
+            predicate,
         },
     ))
 }
 
+/// Parses a comma-separated `SELECT` list of `*`, column names, and `col AS alias`.
+fn parse_select_list(
+    s: &[TokenWithSpan],
+) -> IResult<&[TokenWithSpan], Vec<SelectItem>, SqlParseError> {
+    let (mut s, first) = parse_select_item(s)?;
+    let mut items = vec![first];
+
+    while let Ok((next, _)) = take_token(Token::Comma)(s) {
+        let (next, item) = parse_select_item(next)?;
+        items.push(item);
+        s = next;
+    }
+
+    Ok((s, items))
+}
+
+fn parse_select_item(s: &[TokenWithSpan]) -> IResult<&[TokenWithSpan], SelectItem, SqlParseError> {
+    let (s, item) = match take_token(Token::Wildcard)(s) {
+        Ok((s, _)) => (s, SelectItem::Wildcard),
+        Err(_) => {
+            let (s, name) = take_identifier(s)?;
+            (s, SelectItem::Column(name))
+        }
+    };
+
+    match take_keyword("AS")(s) {
+        Ok((s, _)) => {
+            let (s, alias) = take_identifier(s)?;
+            Ok((
+                s,
+                SelectItem::Aliased {
+                    expr: Box::new(item),
+                    alias,
+                },
+            ))
+        }
+        Err(_) => Ok((s, item)),
+    }
+}
+
+/// Consumes a (possibly empty) run of trailing `JOIN`/`LEFT JOIN ... ON ...` clauses,
+/// folding them onto `source` left-associatively.
+fn parse_joins(
+    s: &[TokenWithSpan],
+    source: DataSource,
+) -> IResult<&[TokenWithSpan], DataSource, SqlParseError> {
+    let mut source = source;
+    let mut s = s;
+    loop {
+        let join_kind = match take_keyword("LEFT JOIN")(s) {
+            Ok((next, _)) => {
+                s = next;
+                JoinKind::Left
+            }
+            Err(_) => match take_keyword("JOIN")(s) {
+                Ok((next, _)) => {
+                    s = next;
+                    JoinKind::Inner
+                }
+                Err(_) => break,
+            },
+        };
+
+        let (next, right_table) = take_identifier(s)?;
+        let (next, _) = take_keyword("ON")(next)?;
+        let (next, left_col) = take_identifier(next)?;
+        let op_offset = offset_of(next);
+        let (next, op) = take_operator(next)?;
+        if op != "=" {
+            return Err(nom::Err::Error(SqlParseError::CustomError {
+                message: format!("join condition must use \"=\", got: {:?}", &op),
+                offset: op_offset,
+            }));
+        }
+        let (next, right_col) = take_identifier(next)?;
+
+        source = DataSource::Join {
+            left: Box::new(source),
+            right: Box::new(DataSource::Datastore { name: right_table }),
+            kind: join_kind,
+            on: (left_col, right_col),
+        };
+        s = next;
+    }
+
+    Ok((s, source))
+}
+
+/// `OR` binds looser than `AND`, which binds looser than comparisons.
+fn parse_or_expr(s: &[TokenWithSpan]) -> IResult<&[TokenWithSpan], Expr, SqlParseError> {
+    let (mut s, mut expr) = parse_and_expr(s)?;
+    while let Ok((next, _)) = take_keyword("OR")(s) {
+        let (next, right) = parse_and_expr(next)?;
+        expr = Expr::Or(Box::new(expr), Box::new(right));
+        s = next;
+    }
+    Ok((s, expr))
+}
+
+fn parse_and_expr(s: &[TokenWithSpan]) -> IResult<&[TokenWithSpan], Expr, SqlParseError> {
+    let (mut s, mut expr) = parse_comparison(s)?;
+    while let Ok((next, _)) = take_keyword("AND")(s) {
+        let (next, right) = parse_comparison(next)?;
+        expr = Expr::And(Box::new(expr), Box::new(right));
+        s = next;
+    }
+    Ok((s, expr))
+}
+
+fn parse_comparison(s: &[TokenWithSpan]) -> IResult<&[TokenWithSpan], Expr, SqlParseError> {
+    let (s, left) = parse_operand(s)?;
+    let (s, op) = take_operator(s)?;
+    let (s, right) = parse_operand(s)?;
+    Ok((s, Expr::Compare { left, op, right }))
+}
+
+fn parse_operand(s: &[TokenWithSpan]) -> IResult<&[TokenWithSpan], Operand, SqlParseError> {
+    let elem = match s.first() {
+        Some(v) => v,
+        None => {
+            return Err(nom::Err::Error(SqlParseError::Eof));
+        }
+    };
+
+    match &elem.token {
+        Token::Identifier(v) => Ok((&s[1..], Operand::ColumnRef(v.to_owned()))),
+        Token::Literal(v) => Ok((&s[1..], Operand::Literal(Value::Str(v.to_owned())))),
+        Token::Number(v) => Ok((&s[1..], Operand::Literal(parse_number_literal(v)))),
+        _ => Err(nom::Err::Error(SqlParseError::CustomError {
+            message: "expected a column reference or a literal".to_owned(),
+            offset: offset_of(s),
+        })),
+    }
+}
+
+/// Parses a `Token::Number`'s digit text into `Value::Int`, falling back to `Value::Float`
+/// only if it overflows `i64` - the lexer only ever produces digit runs, never a decimal
+/// point, so the float case is just a safety net.
+fn parse_number_literal(raw: &str) -> Value {
+    match raw.parse::<i64>() {
+        Ok(v) => Value::Int(v),
+        Err(_) => Value::Float(raw.parse().expect("Number token must be numeric")),
+    }
+}
+
+/// Matches an operator token and returns its symbol.
+fn take_operator(s: &[TokenWithSpan]) -> nom::IResult<&[TokenWithSpan], String, SqlParseError> {
+    let elem = match s.first() {
+        Some(v) => v,
+        None => {
+            return Err(nom::Err::Error(SqlParseError::Eof));
+        }
+    };
+
+    if let Token::Operator(v) = &elem.token {
+        return Ok((&s[1..], v.to_owned()));
+    }
+
+    Err(nom::Err::Error(SqlParseError::CustomError {
+        message: "operator expected".to_owned(),
+        offset: offset_of(s),
+    }))
+}
+
 /// Matches identifier and returns its value.
-fn take_identifier(s: &[Token]) -> nom::IResult<&[Token], String, SqlParseError> {
+fn take_identifier(s: &[TokenWithSpan]) -> nom::IResult<&[TokenWithSpan], String, SqlParseError> {
     let elem = match s.first() {
         Some(v) => v,
         None => {
@@ -103,21 +356,23 @@ fn take_identifier(s: &[Token]) -> nom::IResult<&[Token], String, SqlParseError>
         }
     };
 
-    if let Token::Identifier(v) = elem {
+    if let Token::Identifier(v) = &elem.token {
         return Ok((&s[1..], v.to_owned()));
     }
 
-    Err(nom::Err::Error(SqlParseError::CustomError(
-        "identifier not matched".to_owned(),
-    )))
+    Err(nom::Err::Error(SqlParseError::CustomError {
+        message: "identifier expected".to_owned(),
+        offset: offset_of(s),
+    }))
 }
 
 /// Matches given keyword - case insensitive.
 fn take_keyword(
     name: &str,
-) -> Box<dyn Fn(&[Token]) -> nom::IResult<&[Token], &[Token], SqlParseError>> {
+) -> Box<dyn Fn(&[TokenWithSpan]) -> nom::IResult<&[TokenWithSpan], &[TokenWithSpan], SqlParseError>>
+{
     let name = name.to_owned();
-    Box::new(move |i: &[Token]| {
+    Box::new(move |i: &[TokenWithSpan]| {
         let elem = match i.first() {
             Some(v) => v,
             None => {
@@ -125,23 +380,24 @@ fn take_keyword(
             }
         };
 
-        if let Token::Keyword(v) = elem {
-            if v.to_lowercase() == name.to_lowercase(){
+        if let Token::Keyword(v) = &elem.token {
+            if v.to_lowercase() == name.to_lowercase() {
                 return Ok((&i[1..], &i[..1]));
             }
         }
 
-        Err(nom::Err::Error(SqlParseError::CustomError(format!(
-            "keyword: {:?} not matched",
-            &name
-        ))))
+        Err(nom::Err::Error(SqlParseError::CustomError {
+            message: format!("keyword {:?} expected", &name),
+            offset: offset_of(i),
+        }))
     })
 }
 
 fn take_token(
     token: Token,
-) -> Box<dyn Fn(&[Token]) -> nom::IResult<&[Token], &[Token], SqlParseError>> {
-    Box::new(move |i: &[Token]| {
+) -> Box<dyn Fn(&[TokenWithSpan]) -> nom::IResult<&[TokenWithSpan], &[TokenWithSpan], SqlParseError>>
+{
+    Box::new(move |i: &[TokenWithSpan]| {
         let elem = match i.first() {
             Some(v) => v,
             None => {
@@ -149,14 +405,14 @@ fn take_token(
             }
         };
 
-        if &token == elem {
+        if token == elem.token {
             return Ok((&i[1..], &i[..1]));
         }
 
-        Err(nom::Err::Error(SqlParseError::CustomError(format!(
-            "token: {:?} not matched",
-            &token
-        ))))
+        Err(nom::Err::Error(SqlParseError::CustomError {
+            message: format!("token {:?} expected", &token),
+            offset: offset_of(i),
+        }))
     })
 }
 
@@ -169,11 +425,153 @@ mod tests {
         assert_eq!(
             parse("SELECT x FROM t").unwrap(),
             SelectExpression {
-                columns: vec!["x".to_owned()],
+                items: vec![SelectItem::Column("x".to_owned())],
                 source: DataSource::Datastore {
                     name: "t".to_owned()
-                }
+                },
+                predicate: None,
             }
         );
     }
+
+    #[test]
+    fn test_select_from_where() {
+        assert_eq!(
+            parse("SELECT x FROM t WHERE x = 1").unwrap(),
+            SelectExpression {
+                items: vec![SelectItem::Column("x".to_owned())],
+                source: DataSource::Datastore {
+                    name: "t".to_owned()
+                },
+                predicate: Some(Expr::Compare {
+                    left: Operand::ColumnRef("x".to_owned()),
+                    op: "=".to_owned(),
+                    right: Operand::Literal(Value::Int(1)),
+                }),
+            }
+        );
+    }
+
+    #[test]
+    fn test_select_multiple_columns() {
+        assert_eq!(
+            parse("SELECT x, y FROM t").unwrap().items,
+            vec![
+                SelectItem::Column("x".to_owned()),
+                SelectItem::Column("y".to_owned()),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_select_wildcard() {
+        assert_eq!(
+            parse("SELECT * FROM t").unwrap().items,
+            vec![SelectItem::Wildcard]
+        );
+    }
+
+    #[test]
+    fn test_select_aliased_column() {
+        assert_eq!(
+            parse("SELECT x AS y FROM t").unwrap().items,
+            vec![SelectItem::Aliased {
+                expr: Box::new(SelectItem::Column("x".to_owned())),
+                alias: "y".to_owned(),
+            }]
+        );
+    }
+
+    #[test]
+    fn test_select_join() {
+        assert_eq!(
+            parse("SELECT x FROM t1 JOIN t2 ON a = b").unwrap(),
+            SelectExpression {
+                items: vec![SelectItem::Column("x".to_owned())],
+                source: DataSource::Join {
+                    left: Box::new(DataSource::Datastore {
+                        name: "t1".to_owned()
+                    }),
+                    right: Box::new(DataSource::Datastore {
+                        name: "t2".to_owned()
+                    }),
+                    kind: JoinKind::Inner,
+                    on: ("a".to_owned(), "b".to_owned()),
+                },
+                predicate: None,
+            }
+        );
+    }
+
+    #[test]
+    fn test_select_left_join() {
+        assert_eq!(
+            parse("SELECT x FROM t1 LEFT JOIN t2 ON a = b")
+                .unwrap()
+                .source,
+            DataSource::Join {
+                left: Box::new(DataSource::Datastore {
+                    name: "t1".to_owned()
+                }),
+                right: Box::new(DataSource::Datastore {
+                    name: "t2".to_owned()
+                }),
+                kind: JoinKind::Left,
+                on: ("a".to_owned(), "b".to_owned()),
+            }
+        );
+    }
+
+    #[test]
+    fn test_where_and_or_precedence() {
+        // OR binds looser than AND: `a = 1 AND b = 2 OR c = 3` is `(a = 1 AND b = 2) OR c = 3`
+        assert_eq!(
+            parse("SELECT x FROM t WHERE a = 1 AND b = 2 OR c = 3")
+                .unwrap()
+                .predicate,
+            Some(Expr::Or(
+                Box::new(Expr::And(
+                    Box::new(Expr::Compare {
+                        left: Operand::ColumnRef("a".to_owned()),
+                        op: "=".to_owned(),
+                        right: Operand::Literal(Value::Int(1)),
+                    }),
+                    Box::new(Expr::Compare {
+                        left: Operand::ColumnRef("b".to_owned()),
+                        op: "=".to_owned(),
+                        right: Operand::Literal(Value::Int(2)),
+                    }),
+                )),
+                Box::new(Expr::Compare {
+                    left: Operand::ColumnRef("c".to_owned()),
+                    op: "=".to_owned(),
+                    right: Operand::Literal(Value::Int(3)),
+                }),
+            ))
+        );
+    }
+
+    #[test]
+    fn test_quoted_numeric_literal_stays_a_string() {
+        // a quoted literal is always a `Value::Str`, even when its text looks numeric -
+        // only a bare `Token::Number` goes through numeric inference.
+        assert_eq!(
+            parse(r#"SELECT x FROM t WHERE code = "007""#)
+                .unwrap()
+                .predicate,
+            Some(Expr::Compare {
+                left: Operand::ColumnRef("code".to_owned()),
+                op: "=".to_owned(),
+                right: Operand::Literal(Value::Str("007".to_owned())),
+            })
+        );
+    }
+
+    #[test]
+    fn test_parse_error_reports_offset_and_snippet() {
+        let err = parse("SELECT 1 FROM t").unwrap_err();
+        assert!(err.contains("identifier expected at offset 7"));
+        assert!(err.contains("SELECT 1 FROM t"));
+        assert!(err.contains('^'));
+    }
 }